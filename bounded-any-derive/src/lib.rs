@@ -0,0 +1,129 @@
+//! `#[derive(AsStatic)]`: generates `unsafe impl AsStatic` for a user struct or enum by reusing
+//! the type itself as its own `Static` associated type, with every lifetime parameter replaced by
+//! `'static` and every generic type parameter `F` replaced by `<F as AsStatic>::Static`. This is
+//! exactly the pattern `bounded-any`'s manual impls for tuples, `Vec`, and arrays already follow;
+//! the derive just does it once, generically, for arbitrary user types.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use syn::{Data, DeriveInput, Fields, GenericParam, Type};
+
+#[proc_macro_derive(AsStatic)]
+pub fn derive_as_static(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("#[derive(AsStatic)] expects a valid item");
+
+    if let Err(e) = reject_unsound_fields(&input.data) {
+        return e.to_compile_error().into();
+    }
+
+    let name = &input.ident;
+
+    // The `Static` type is `Name<'static, .., T1::Static, ..>` -- the same type, with every
+    // lifetime replaced by `'static` and every type parameter replaced by its own `Static`.
+    let static_args = input.generics.params.iter().map(|param| match *param {
+        GenericParam::Lifetime(_) => quote!('static),
+        GenericParam::Type(ref t) => {
+            let ident = &t.ident;
+            quote!(<#ident as ::bounded_any::AsStatic>::Static)
+        }
+        GenericParam::Const(ref c) => {
+            let ident = &c.ident;
+            quote!(#ident)
+        }
+    });
+
+    // The impl stays generic over the original lifetimes and type parameters, with an `AsStatic`
+    // bound added to every type parameter the user declared, plus a `Sized` bound on each type
+    // parameter's `Static`, since it is going to be used by value in the associated type above.
+    // Both bounds are threaded onto the real `unsafe impl`'s own generics/where-clause, not some
+    // separate helper item, so that they actually constrain the impl that needs them.
+    let mut generics = input.generics.clone();
+    for param in generics.params.iter_mut() {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            type_param.bounds.push(syn::parse_quote!(::bounded_any::AsStatic));
+        }
+    }
+    {
+        let where_clause = generics.make_where_clause();
+        for type_param in input.generics.type_params() {
+            let ident = &type_param.ident;
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(<#ident as ::bounded_any::AsStatic>::Static: ::std::marker::Sized));
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let expanded = quote! {
+        unsafe impl #impl_generics ::bounded_any::AsStatic for #name #ty_generics #where_clause {
+            type Static = #name<#(#static_args),*>;
+        }
+    };
+
+    expanded.into()
+}
+
+/// `#[derive(AsStatic)]` relies on the `Static` type being laid out identically to `Self`, save
+/// for its lifetimes -- a raw pointer field breaks that, since its pointee isn't tracked by
+/// `AsStatic` at all and could easily end up dangling once "relifetimed". Reject those up front
+/// with a clear error instead of generating something unsound.
+fn reject_unsound_fields(data: &Data) -> Result<(), syn::Error> {
+    match *data {
+        Data::Struct(ref s) => check_fields(&s.fields),
+        Data::Enum(ref e) => {
+            for variant in &e.variants {
+                check_fields(&variant.fields)?;
+            }
+            Ok(())
+        }
+        Data::Union(ref u) => {
+            let named = Fields::Named(u.fields.clone());
+            check_fields(&named)
+        }
+    }
+}
+
+fn check_fields(fields: &Fields) -> Result<(), syn::Error> {
+    for field in fields.iter() {
+        if contains_raw_pointer(&field.ty) {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "#[derive(AsStatic)] cannot be used on a type containing a raw pointer: \
+                 its pointee isn't tracked by `AsStatic`, so the generated impl would not \
+                 be sound",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn contains_raw_pointer(ty: &Type) -> bool {
+    match *ty {
+        Type::Ptr(_) => true,
+        Type::Reference(ref r) => contains_raw_pointer(&r.elem),
+        Type::Array(ref a) => contains_raw_pointer(&a.elem),
+        Type::Slice(ref s) => contains_raw_pointer(&s.elem),
+        Type::Tuple(ref t) => t.elems.iter().any(contains_raw_pointer),
+        Type::Path(ref p) => p.path.segments.iter().any(|segment| {
+            use syn::PathArguments;
+            match segment.arguments {
+                PathArguments::AngleBracketed(ref args) => args.args.iter().any(|arg| {
+                    if let syn::GenericArgument::Type(ref t) = *arg {
+                        contains_raw_pointer(t)
+                    } else {
+                        false
+                    }
+                }),
+                _ => false,
+            }
+        }),
+        _ => false,
+    }
+}
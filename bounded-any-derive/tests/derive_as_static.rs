@@ -0,0 +1,39 @@
+extern crate bounded_any;
+#[macro_use]
+extern crate bounded_any_derive;
+
+use bounded_any::AsStatic;
+
+// A lifetime parameter and a generic type parameter, the latter used both by reference and by
+// value.
+#[derive(AsStatic)]
+struct Foo<'a, T> {
+    x: &'a T,
+    y: Vec<T>,
+}
+
+#[derive(AsStatic)]
+struct Unit;
+
+#[derive(AsStatic)]
+enum Either<'a, T> {
+    Left(&'a T),
+    Right(T),
+}
+
+fn assert_as_static<T: AsStatic>() {}
+
+#[test]
+fn derives_as_static_for_struct_with_lifetime_and_type_param() {
+    assert_as_static::<Foo<'static, i32>>();
+}
+
+#[test]
+fn derives_as_static_for_unit_struct() {
+    assert_as_static::<Unit>();
+}
+
+#[test]
+fn derives_as_static_for_enum() {
+    assert_as_static::<Either<'static, i32>>();
+}
@@ -2,6 +2,23 @@ use std::any::{Any, TypeId};
 use std::marker::PhantomData;
 use std::rc::{Rc, Weak};
 
+#[cfg(feature = "derive")]
+extern crate bounded_any_derive;
+
+mod map;
+mod provider;
+mod sync;
+
+pub use map::BoundedAnyMap;
+pub use provider::{BoundedDemand, BoundedProvider, request_ref, request_value};
+pub use sync::{BoundedAnyMutSync, BoundedAnyRefSync};
+
+/// Derives `AsStatic` for a struct or enum by reusing it as its own `Static` type, with every
+/// lifetime parameter replaced by `'static` and every generic type parameter `F` replaced by
+/// `F::Static`. Requires the `derive` feature.
+#[cfg(feature = "derive")]
+pub use bounded_any_derive::AsStatic;
+
 
 /// A `TypeId` combined with a phantom lifetime, to preserve whatever lifetime the initial data
 /// might have had.
@@ -96,8 +113,10 @@ impl<'a> BoundedAnyMut<'a> {
     }
 
 
-    /// Try to downcast to a mutable reference to the correctly-lifetimed `T`.
-    pub fn downcast_mut<T: 'a + AsStatic>(&mut self) -> Option<&'a mut T>
+    /// Try to downcast to a mutable reference to the correctly-lifetimed `T`. Borrowed from
+    /// `&mut self` rather than tied to the phantom `'a`, so that two overlapping calls can't each
+    /// hand back a live `&mut T` aliasing the same underlying value.
+    pub fn downcast_mut<T: 'a + AsStatic>(&mut self) -> Option<&mut T>
         where T::Static: Sized
     {
         unsafe {
@@ -109,6 +128,80 @@ impl<'a> BoundedAnyMut<'a> {
 }
 
 
+/// Represents an owned value which can be cast to a `'static` lifetime and then used with `Any`.
+/// Supports similar operations to `Box<Any>`, plus a `downcast` which recovers the original,
+/// correctly-lifetimed owned value.
+pub struct BoundedAnyBox<'a>(Box<Any>, TypeId, PhantomData<&'a ()>);
+
+
+impl<'a, T: 'a + AsStatic> From<Box<T>> for BoundedAnyBox<'a>
+    where T::Static: Sized
+{
+    fn from(t_box: Box<T>) -> BoundedAnyBox<'a> {
+        BoundedAnyBox(unsafe { Box::from_raw(Box::into_raw(t_box) as *mut T::Static) },
+                      TypeId::of::<T::Static>(),
+                      PhantomData)
+    }
+}
+
+
+impl<'a> BoundedAnyBox<'a> {
+    /// Check whether the underlying type is a `T`, disregarding lifetimes. This operation is
+    /// duplicated from `BoundedAnyRef` since there does not appear to be a way to hook into Rust's
+    /// pointer weakening coercions.
+    pub fn is<T: 'a + AsStatic>(&self) -> bool
+        where T::Static: Sized
+    {
+        self.0.is::<T::Static>()
+    }
+
+
+    /// Try to downcast to a reference to the correctly-lifetimed `T`.
+    pub fn downcast_ref<T: 'a + AsStatic>(&self) -> Option<&'a T>
+        where T::Static: Sized
+    {
+        unsafe {
+            self.0
+                .downcast_ref::<T::Static>()
+                .map(|opt_static| &*(opt_static as *const T::Static as *const T))
+        }
+    }
+
+
+    /// Try to downcast to a mutable reference to the correctly-lifetimed `T`. Borrowed from
+    /// `&mut self` rather than tied to the phantom `'a`, so that two overlapping calls can't each
+    /// hand back a live `&mut T` aliasing the same stored value.
+    pub fn downcast_mut<T: 'a + AsStatic>(&mut self) -> Option<&mut T>
+        where T::Static: Sized
+    {
+        unsafe {
+            self.0
+                .downcast_mut::<T::Static>()
+                .map(|opt_static| &mut *(opt_static as *mut T::Static as *mut T))
+        }
+    }
+
+
+    /// Try to downcast to the original, correctly-lifetimed owned value. On failure, the
+    /// `BoundedAnyBox` is handed back unchanged so the erased value is not lost.
+    ///
+    /// This is the only safe way to recover `T` from a `BoundedAnyBox`. The phantom lifetime `'a`
+    /// is otherwise just an upper bound on the real data, which is kept alive erased to `'static`
+    /// inside; dropping the `BoundedAnyBox` runs `T::Static`'s destructor through `Box<Any>`'s
+    /// `Drop` impl, which is sound because `T` and `T::Static` are required to differ only in
+    /// their lifetimes, and so share layout and drop behavior.
+    pub fn downcast<T: 'a + AsStatic>(self) -> Result<Box<T>, Self>
+        where T::Static: Sized
+    {
+        let BoundedAnyBox(any_box, type_id, _marker) = self;
+        match any_box.downcast::<T::Static>() {
+            Ok(t_static) => Ok(unsafe { Box::from_raw(Box::into_raw(t_static) as *mut T) }),
+            Err(any_box) => Err(BoundedAnyBox(any_box, type_id, PhantomData)),
+        }
+    }
+}
+
+
 pub unsafe trait AsStatic {
     type Static: ?Sized + Any + 'static;
 }
@@ -224,3 +317,142 @@ unsafe impl<T: AsStatic> AsStatic for Rc<T> {
 unsafe impl<T: AsStatic> AsStatic for Weak<T> {
     type Static = Weak<T::Static>;
 }
+
+
+/// Implemented for `dyn Tr` by `bounded_trait_object!`, which registers the concrete types
+/// reachable as implementors of `Tr` through `BoundedAnyRef::downcast_trait`.
+pub trait BoundedTraitObject {
+    /// If `type_id` is the `TypeId` of some registered implementor's `Static` type, return a
+    /// function that casts the erased pointer stored for it to `*const Self`.
+    #[doc(hidden)]
+    fn caster(type_id: TypeId) -> Option<fn(*const ()) -> *const Self>;
+}
+
+
+impl<'a> BoundedAnyRef<'a> {
+    /// Try to downcast to a `&'a dyn Tr`, for some trait object `dyn Tr` registered with
+    /// `bounded_trait_object!`. Only concrete types registered as implementors of `Tr` are
+    /// reachable this way, so the vtable attached to the returned reference is always the one
+    /// belonging to the value's real, stored type.
+    pub fn downcast_trait<Tr: ?Sized + BoundedTraitObject>(&self) -> Option<&'a Tr> {
+        Tr::caster(self.1).map(|cast| unsafe { &*cast(self.0 as *const Any as *const ()) })
+    }
+}
+
+
+/// Register the concrete implementors of a trait so that `BoundedAnyRef::downcast_trait` can
+/// produce a `&dyn Tr` for any of them, the bounded analogue of the trait-upcasting coercions std
+/// is adding for `dyn Any`.
+///
+/// Registered types that have a lifetime parameter must be written with the elided lifetime
+/// (`Foo<'_>`), not a named one (`Foo<'a>`): the generated impl has nowhere to declare a named
+/// lifetime, but `'_` is inferred locally wherever the macro actually uses the type.
+///
+/// ```
+/// #[macro_use]
+/// extern crate bounded_any;
+///
+/// use bounded_any::{AsStatic, BoundedAnyRef};
+///
+/// trait Greet {
+///     fn greet(&self) -> String;
+/// }
+///
+/// struct Hello<'a>(&'a str);
+///
+/// unsafe impl<'a> AsStatic for Hello<'a> {
+///     type Static = Hello<'static>;
+/// }
+///
+/// impl<'a> Greet for Hello<'a> {
+///     fn greet(&self) -> String {
+///         format!("hello, {}", self.0)
+///     }
+/// }
+///
+/// bounded_trait_object!(Greet => [Hello<'_>]);
+///
+/// fn main() {
+///     let name = String::from("world");
+///     let hello = Hello(&name);
+///     let any_ref = BoundedAnyRef::from(&hello);
+///     let greeter = any_ref.downcast_trait::<dyn Greet>().unwrap();
+///     assert_eq!(greeter.greet(), "hello, world");
+/// }
+/// ```
+#[macro_export]
+macro_rules! bounded_trait_object {
+    ($tr:path => [$($ty:ty),+ $(,)*]) => {
+        impl $crate::BoundedTraitObject for dyn $tr {
+            fn caster(type_id: ::std::any::TypeId) -> Option<fn(*const ()) -> *const Self> {
+                $(
+                    if type_id == ::std::any::TypeId::of::<<$ty as $crate::AsStatic>::Static>() {
+                        return Some(|erased: *const ()| unsafe {
+                            &*(erased as *const $ty) as &$tr as *const $tr
+                        });
+                    }
+                )+
+                None
+            }
+        }
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_any_box_roundtrips_through_ref_mut_and_owned() {
+        let mut boxed = BoundedAnyBox::from(Box::new(5i32));
+
+        assert!(boxed.downcast_ref::<i32>().is_some());
+        assert!(boxed.downcast_ref::<u8>().is_none());
+
+        *boxed.downcast_mut::<i32>().unwrap() += 1;
+        assert_eq!(*boxed.downcast_ref::<i32>().unwrap(), 6);
+
+        let owned = match boxed.downcast::<i32>() {
+            Ok(owned) => owned,
+            Err(_) => panic!("downcast to the type it was constructed with should succeed"),
+        };
+        assert_eq!(*owned, 6);
+    }
+
+    #[test]
+    fn bounded_any_box_downcast_mut_does_not_alias() {
+        // Two sequential, non-overlapping calls both see the same, single underlying value --
+        // the bug this guards against let both live at once, aliasing the same storage.
+        let mut boxed = BoundedAnyBox::from(Box::new(String::from("a")));
+
+        {
+            let first = boxed.downcast_mut::<String>().unwrap();
+            first.push_str("b");
+        }
+        {
+            let second = boxed.downcast_mut::<String>().unwrap();
+            second.push_str("c");
+        }
+
+        assert_eq!(boxed.downcast_ref::<String>().unwrap(), "abc");
+    }
+
+    #[test]
+    fn bounded_any_ref_and_mut_downcast() {
+        let mut value = 42i32;
+
+        {
+            let any_ref = BoundedAnyRef::from(&value);
+            assert!(any_ref.is::<i32>());
+            assert_eq!(*any_ref.downcast_ref::<i32>().unwrap(), 42);
+        }
+
+        {
+            let mut any_mut = BoundedAnyMut::from(&mut value);
+            *any_mut.downcast_mut::<i32>().unwrap() += 1;
+        }
+
+        assert_eq!(value, 43);
+    }
+}
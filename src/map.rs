@@ -0,0 +1,100 @@
+//! A heterogeneous map keyed by type, in the style of the `anymap`/`typemap` crates, except that
+//! every value stored in it shares one phantom lifetime `'a` and so need not be `'static`.
+
+use std::collections::HashMap;
+
+use {AsStatic, BoundedAnyBox, BoundedTypeId};
+
+
+/// A map from types to values of that type, where every value shares the phantom lifetime `'a`.
+/// Built on `BoundedTypeId` and `BoundedAnyBox`, so unlike `anymap`'s map it can hold borrowed,
+/// non-`'static` data.
+#[derive(Default)]
+pub struct BoundedAnyMap<'a>(HashMap<BoundedTypeId<'a>, BoundedAnyBox<'a>>);
+
+
+impl<'a> BoundedAnyMap<'a> {
+    /// Create an empty `BoundedAnyMap`.
+    pub fn new() -> BoundedAnyMap<'a> {
+        BoundedAnyMap(HashMap::new())
+    }
+
+
+    /// Insert a value of type `T`, returning the previous value stored for `T`, if any.
+    pub fn insert<T: 'a + AsStatic>(&mut self, value: T) -> Option<T>
+        where T::Static: Sized
+    {
+        self.0
+            .insert(BoundedTypeId::of::<T>(), BoundedAnyBox::from(Box::new(value)))
+            .and_then(|old| old.downcast::<T>().ok().map(|boxed| *boxed))
+    }
+
+
+    /// Get a reference to the stored value of type `T`, if any.
+    pub fn get<T: 'a + AsStatic>(&self) -> Option<&'a T>
+        where T::Static: Sized
+    {
+        self.0.get(&BoundedTypeId::of::<T>()).and_then(|any| any.downcast_ref::<T>())
+    }
+
+
+    /// Get a mutable reference to the stored value of type `T`, if any. Borrowed from `&mut self`
+    /// rather than tied to the phantom `'a`, so that two overlapping calls can't each hand back a
+    /// live `&mut T` aliasing the same stored value.
+    pub fn get_mut<T: 'a + AsStatic>(&mut self) -> Option<&mut T>
+        where T::Static: Sized
+    {
+        self.0.get_mut(&BoundedTypeId::of::<T>()).and_then(|any| any.downcast_mut::<T>())
+    }
+
+
+    /// Remove and return the stored value of type `T`, if any.
+    pub fn remove<T: 'a + AsStatic>(&mut self) -> Option<T>
+        where T::Static: Sized
+    {
+        self.0
+            .remove(&BoundedTypeId::of::<T>())
+            .and_then(|any| any.downcast::<T>().ok().map(|boxed| *boxed))
+    }
+
+
+    /// Check whether a value of type `T` is stored in the map.
+    pub fn contains<T: 'a + AsStatic>(&self) -> bool
+        where T::Static: Sized
+    {
+        self.0.contains_key(&BoundedTypeId::of::<T>())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_remove_roundtrip() {
+        let mut map = BoundedAnyMap::new();
+
+        assert!(!map.contains::<i32>());
+        assert_eq!(map.insert(5i32), None);
+        assert!(map.contains::<i32>());
+        assert_eq!(map.insert(6i32), Some(5));
+        assert_eq!(*map.get::<i32>().unwrap(), 6);
+
+        assert_eq!(map.remove::<i32>(), Some(6));
+        assert!(!map.contains::<i32>());
+    }
+
+    #[test]
+    fn get_mut_does_not_alias() {
+        // Two sequential, non-overlapping `get_mut` calls both see the same, single stored value
+        // -- the bug this guards against let both live at once, aliasing the same storage.
+        let mut map = BoundedAnyMap::new();
+        map.insert(5i32);
+
+        *map.get_mut::<i32>().unwrap() += 1;
+        *map.get_mut::<i32>().unwrap() += 1;
+
+        assert_eq!(*map.get::<i32>().unwrap(), 7);
+    }
+}
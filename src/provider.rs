@@ -0,0 +1,134 @@
+//! A lifetime-bounded version of the `provide`/`request` pattern from `core::any`: a type can
+//! expose several internal references or values by type, without requiring any of them to be
+//! `'static`.
+
+use std::any::{Any, TypeId};
+
+use AsStatic;
+
+
+/// An erased, tag-checked slot that a `BoundedProvider` fills in if it is asked for the type the
+/// slot's tag represents. The `TypeId` tag is always a `'static` one -- either `T::Static`'s, for
+/// a `request_value::<T>`, or `&'static T::Static`'s, for a `request_ref::<T>` -- even though the
+/// value that ends up in the slot is only valid for the caller's original, non-`'static` `'a`.
+struct TaggedOption(Option<Box<Any>>, TypeId);
+
+
+/// A request for a value of some bounded type, passed to `BoundedProvider::provide` so that it
+/// can fill in any of the references or values it has on offer. Mirrors `core::any::Demand`,
+/// except that the erased slot it points to is matched against `TaggedOption`, not `Any` itself,
+/// since the value behind it is not always `'static`.
+pub struct BoundedDemand<'a>(&'a mut Any);
+
+
+impl<'a> BoundedDemand<'a> {
+    /// If `self` is requesting a `&T`, and has not already been satisfied, answer it with
+    /// `value`.
+    pub fn provide_ref<T: 'a + AsStatic>(&mut self, value: &'a T) -> &mut Self
+        where T::Static: Sized
+    {
+        if let Some(tagged) = self.0.downcast_mut::<TaggedOption>() {
+            if tagged.0.is_none() && tagged.1 == TypeId::of::<&'static T::Static>() {
+                let erased: &'static T::Static = unsafe {
+                    &*(value as *const T as *const T::Static)
+                };
+                tagged.0 = Some(Box::new(erased));
+            }
+        }
+
+        self
+    }
+
+
+    /// If `self` is requesting a `T` by value, and has not already been satisfied, answer it with
+    /// `value`.
+    pub fn provide_value<T: 'a + AsStatic>(&mut self, value: T) -> &mut Self
+        where T::Static: Sized
+    {
+        if let Some(tagged) = self.0.downcast_mut::<TaggedOption>() {
+            if tagged.0.is_none() && tagged.1 == TypeId::of::<T::Static>() {
+                let erased: Box<T::Static> = unsafe {
+                    Box::from_raw(Box::into_raw(Box::new(value)) as *mut T::Static)
+                };
+                tagged.0 = Some(erased);
+            }
+        }
+
+        self
+    }
+}
+
+
+/// Implemented by types which can hand out several of their fields by type, to be recovered with
+/// `request_ref`/`request_value`. The bounded analogue of `core::any::Provider`.
+pub trait BoundedProvider<'a> {
+    /// Fill in `demand` with whichever of `self`'s fields answer what it's asking for.
+    fn provide(&'a self, demand: &mut BoundedDemand<'a>);
+}
+
+
+/// Borrow `tagged` as a `&'a mut Any`, even though its real, local storage only lives for the
+/// duration of `request_ref`/`request_value`. This is sound exactly because `BoundedProvider`'s
+/// contract (like `core::any::Provider`'s) is that `provide` only ever uses the demand it is
+/// handed for the duration of the call, and never squirrels away the reference past it -- the
+/// same "narrow, use, and let the borrow end" discipline the rest of this crate relies on when it
+/// casts a short-lived reference to `T::Static` and back.
+unsafe fn erase_local<'a>(tagged: &mut TaggedOption) -> &'a mut Any {
+    &mut *(tagged as *mut TaggedOption)
+}
+
+
+/// Request a reference to a `T` from `provider`, if it has one to give.
+pub fn request_ref<'a, T, P>(provider: &'a P) -> Option<&'a T>
+    where T: 'a + AsStatic,
+          T::Static: Sized,
+          P: BoundedProvider<'a> + ?Sized
+{
+    let mut tagged = TaggedOption(None, TypeId::of::<&'static T::Static>());
+    provider.provide(&mut BoundedDemand(unsafe { erase_local(&mut tagged) }));
+    tagged.0.and_then(|erased| erased.downcast::<&'static T::Static>().ok()).map(|boxed| unsafe {
+        &*(*boxed as *const T::Static as *const T)
+    })
+}
+
+
+/// Request a `T` by value from `provider`, if it has one to give.
+pub fn request_value<'a, T, P>(provider: &'a P) -> Option<T>
+    where T: 'a + AsStatic,
+          T::Static: Sized,
+          P: BoundedProvider<'a> + ?Sized
+{
+    let mut tagged = TaggedOption(None, TypeId::of::<T::Static>());
+    provider.provide(&mut BoundedDemand(unsafe { erase_local(&mut tagged) }));
+    tagged.0.and_then(|erased| erased.downcast::<T::Static>().ok()).map(|boxed| unsafe {
+        *Box::from_raw(Box::into_raw(boxed) as *mut T)
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Greeting<'a> {
+        count: &'a i32,
+    }
+
+    impl<'a> BoundedProvider<'a> for Greeting<'a> {
+        fn provide(&'a self, demand: &mut BoundedDemand<'a>) {
+            demand
+                .provide_ref(self.count)
+                .provide_value(format!("count is {}", self.count));
+        }
+    }
+
+    #[test]
+    fn requests_ref_and_value_from_provider() {
+        let count = 5i32;
+        let greeting = Greeting { count: &count };
+
+        assert_eq!(request_ref::<i32, _>(&greeting), Some(&5));
+        assert_eq!(request_value::<String, _>(&greeting), Some(String::from("count is 5")));
+        assert_eq!(request_value::<u8, _>(&greeting), None);
+    }
+}
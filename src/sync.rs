@@ -0,0 +1,152 @@
+//! Thread-safe analogues of `BoundedAnyRef`/`BoundedAnyMut`, plus `AsStatic` impls for the
+//! threaded container types (`Arc`, `sync::Weak`, `Mutex`, `RwLock`) that the base crate omits.
+//! The base types erase to `&'a Any`, which is neither `Send` nor `Sync`; these erase to
+//! `&'a (Any + Send + Sync)` instead, so a bounded-any value can cross thread boundaries.
+
+use std::any::{Any, TypeId};
+use std::sync::{self, Arc, Mutex, RwLock};
+
+use AsStatic;
+
+
+unsafe impl<T: AsStatic> AsStatic for Arc<T> {
+    type Static = Arc<T::Static>;
+}
+
+
+unsafe impl<T: AsStatic> AsStatic for sync::Weak<T> {
+    type Static = sync::Weak<T::Static>;
+}
+
+
+unsafe impl<T: AsStatic> AsStatic for Mutex<T> {
+    type Static = Mutex<T::Static>;
+}
+
+
+unsafe impl<T: AsStatic> AsStatic for RwLock<T> {
+    type Static = RwLock<T::Static>;
+}
+
+
+/// Represents an immutable reference to a type which can be cast to a `'static` lifetime and then
+/// used with `Any + Send + Sync`. The thread-safe analogue of `BoundedAnyRef`.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundedAnyRefSync<'a>(&'a (Any + Send + Sync), TypeId);
+
+
+impl<'a, T: 'a + AsStatic> From<&'a T> for BoundedAnyRefSync<'a>
+    where T::Static: Sized + Send + Sync
+{
+    fn from(t_ref: &'a T) -> BoundedAnyRefSync<'a> {
+        BoundedAnyRefSync(unsafe { &*(t_ref as *const T as *const T::Static) },
+                           TypeId::of::<T::Static>())
+    }
+}
+
+
+impl<'a> BoundedAnyRefSync<'a> {
+    /// Check whether the underlying type is a `T`, disregarding lifetimes.
+    pub fn is<T: 'a + AsStatic>(&self) -> bool
+        where T::Static: Sized
+    {
+        self.0.is::<T::Static>()
+    }
+
+
+    /// Try to downcast to a reference to the correctly-lifetimed `T`.
+    pub fn downcast_ref<T: 'a + AsStatic>(&self) -> Option<&'a T>
+        where T::Static: Sized
+    {
+        unsafe {
+            self.0
+                .downcast_ref::<T::Static>()
+                .map(|opt_static| &*(opt_static as *const T::Static as *const T))
+        }
+    }
+}
+
+
+/// Represents a mutable reference to a type which can be cast to a `'static` lifetime and then
+/// used with `Any + Send + Sync`. The thread-safe analogue of `BoundedAnyMut`.
+#[derive(Debug)]
+pub struct BoundedAnyMutSync<'a>(&'a mut (Any + Send + Sync), TypeId);
+
+
+impl<'a, T: 'a + AsStatic> From<&'a mut T> for BoundedAnyMutSync<'a>
+    where T::Static: Sized + Send + Sync
+{
+    fn from(t_ref: &'a mut T) -> BoundedAnyMutSync<'a> {
+        BoundedAnyMutSync(unsafe { &mut *(t_ref as *mut T as *mut T::Static) },
+                           TypeId::of::<T::Static>())
+    }
+}
+
+
+impl<'a> BoundedAnyMutSync<'a> {
+    /// Check whether the underlying type is a `T`, disregarding lifetimes. This operation is
+    /// duplicated from `BoundedAnyRefSync` since there does not appear to be a way to hook into
+    /// Rust's pointer weakening coercions.
+    pub fn is<T: 'a + AsStatic>(&self) -> bool
+        where T::Static: Sized
+    {
+        self.0.is::<T::Static>()
+    }
+
+
+    /// Try to downcast to a reference to the correctly-lifetimed `T`. This operation is
+    /// duplicated from `BoundedAnyRefSync` since there does not appear to be a way to hook into
+    /// Rust's pointer weakening coercions.
+    pub fn downcast_ref<T: 'a + AsStatic>(&self) -> Option<&'a T>
+        where T::Static: Sized
+    {
+        unsafe {
+            self.0
+                .downcast_ref::<T::Static>()
+                .map(|opt_static| &*(opt_static as *const T::Static as *const T))
+        }
+    }
+
+
+    /// Try to downcast to a mutable reference to the correctly-lifetimed `T`. Borrowed from
+    /// `&mut self` rather than tied to the phantom `'a`, so that two overlapping calls can't each
+    /// hand back a live `&mut T` aliasing the same underlying value.
+    pub fn downcast_mut<T: 'a + AsStatic>(&mut self) -> Option<&mut T>
+        where T::Static: Sized
+    {
+        unsafe {
+            self.0
+                .downcast_mut::<T::Static>()
+                .map(|opt_static| &mut *(opt_static as *mut T::Static as *mut T))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ref_sync_downcast() {
+        let value = 5i32;
+        let any_ref = BoundedAnyRefSync::from(&value);
+
+        assert!(any_ref.is::<i32>());
+        assert!(!any_ref.is::<u8>());
+        assert_eq!(*any_ref.downcast_ref::<i32>().unwrap(), 5);
+    }
+
+    #[test]
+    fn mut_sync_downcast_mut_does_not_alias() {
+        // Two sequential, non-overlapping calls both see the same, single underlying value -- the
+        // bug this guards against let both live at once, aliasing the same storage.
+        let mut value = 5i32;
+        let mut any_mut = BoundedAnyMutSync::from(&mut value);
+
+        *any_mut.downcast_mut::<i32>().unwrap() += 1;
+        *any_mut.downcast_mut::<i32>().unwrap() += 1;
+
+        assert_eq!(*any_mut.downcast_ref::<i32>().unwrap(), 7);
+    }
+}